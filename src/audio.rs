@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::mpsc::{channel, Receiver};
+
+/// Configuration for the optional audio track muxed alongside video.
+pub struct AudioOptions {
+    /// Extract a single channel out of a stereo capture (`0` = left,
+    /// `1` = right) into a mono track instead of muxing all channels —
+    /// useful when a lavalier mic is on one channel and the camera's
+    /// built-in mic is on the other.
+    pub mono_from_channel: Option<usize>,
+}
+
+/// Captures audio from the system's default input device (ALSA/PulseAudio
+/// via `cpal`) and forwards interleaved `f32` sample buffers to the caller
+/// over a channel, mirroring how `MmapStream` hands video frames to the
+/// capture loop.
+pub struct AudioCapture {
+    stream: cpal::Stream,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Receiver<Vec<f32>>,
+}
+
+impl AudioCapture {
+    /// Opens the default input device and starts capturing immediately.
+    pub fn start() -> Result<Self> {
+        let host = cpal::default_host();
+        let device = host
+            .default_input_device()
+            .context("no input audio device available")?;
+        let config = device
+            .default_input_config()
+            .context("no default input config for audio device")?;
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels();
+
+        let (tx, rx) = channel();
+        let stream = device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let _ = tx.send(data.to_vec());
+            },
+            |err| eprintln!("audio capture stream error: {}", err),
+            None,
+        )?;
+        stream.play()?;
+
+        Ok(Self {
+            stream,
+            sample_rate,
+            channels,
+            samples: rx,
+        })
+    }
+}
+
+/// Extracts a single channel out of an interleaved multi-channel buffer,
+/// producing a mono buffer. Used to implement `mono_from_channel`.
+pub fn extract_channel(interleaved: &[f32], channels: u16, channel: usize) -> Vec<f32> {
+    interleaved
+        .chunks(channels as usize)
+        .filter_map(|frame| frame.get(channel).copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_channel_picks_out_one_channel_of_interleaved_stereo() {
+        let interleaved = vec![1.0, -1.0, 2.0, -2.0, 3.0, -3.0];
+        assert_eq!(extract_channel(&interleaved, 2, 0), vec![1.0, 2.0, 3.0]);
+        assert_eq!(extract_channel(&interleaved, 2, 1), vec![-1.0, -2.0, -3.0]);
+    }
+
+    #[test]
+    fn extract_channel_keeps_only_the_channels_a_trailing_partial_frame_has() {
+        let interleaved = vec![1.0, -1.0, 2.0];
+        assert_eq!(extract_channel(&interleaved, 2, 0), vec![1.0, 2.0]);
+        assert_eq!(extract_channel(&interleaved, 2, 1), vec![-1.0]);
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use image::RgbImage;
+use v4l::format::FourCC;
+use v4l::framesize::FrameSizeEnum;
+use v4l::video::Capture;
+use v4l::Device;
+
+/// Pixel format negotiated with the capture device for a requested
+/// resolution. MJPG is preferred (it's what the rest of the pipeline was
+/// written against), but not every webcam offers it at every resolution,
+/// so we fall back to raw YUYV and decode it ourselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Mjpg,
+    Yuyv,
+}
+
+impl PixelFormat {
+    fn fourcc(self) -> FourCC {
+        match self {
+            PixelFormat::Mjpg => FourCC::new(b"MJPG"),
+            PixelFormat::Yuyv => FourCC::new(b"YUYV"),
+        }
+    }
+}
+
+/// Checks whether `dev` offers `fourcc` at exactly `width`x`height`.
+fn supports_resolution(dev: &Device, fourcc: FourCC, width: u32, height: u32) -> bool {
+    let Ok(sizes) = dev.enum_framesizes(fourcc) else {
+        return false;
+    };
+    sizes.into_iter().any(|size| match size.size {
+        FrameSizeEnum::Discrete(d) => d.width == width && d.height == height,
+        FrameSizeEnum::Stepwise(s) => {
+            (s.min_width..=s.max_width).contains(&width)
+                && (s.min_height..=s.max_height).contains(&height)
+        }
+    })
+}
+
+/// Probes `dev`'s supported formats and frame sizes and picks MJPG when
+/// it's offered at `width`x`height`, otherwise falls back to YUYV so the
+/// capture still works on cameras that don't expose MJPG at that
+/// resolution. Call [`apply_format`] with the result before streaming.
+pub fn negotiate_format(dev: &Device, width: u32, height: u32) -> Result<PixelFormat> {
+    let descriptions = dev
+        .enum_formats()
+        .context("failed to enumerate pixel formats")?;
+    let offers = |format: PixelFormat| {
+        descriptions.iter().any(|d| d.fourcc == format.fourcc())
+            && supports_resolution(dev, format.fourcc(), width, height)
+    };
+
+    if offers(PixelFormat::Mjpg) {
+        Ok(PixelFormat::Mjpg)
+    } else if offers(PixelFormat::Yuyv) {
+        Ok(PixelFormat::Yuyv)
+    } else {
+        anyhow::bail!(
+            "camera offers neither MJPG nor YUYV at {}x{}",
+            width,
+            height
+        )
+    }
+}
+
+/// Sets `dev`'s capture format to `format` at `width`x`height` and returns
+/// the format actually applied. `VIDIOC_S_FMT` lets the driver clamp or
+/// round the requested resolution rather than reject it outright, so
+/// callers must decode/allocate against the returned size, not the
+/// requested one.
+pub fn apply_format(dev: &Device, format: PixelFormat, width: u32, height: u32) -> Result<(u32, u32)> {
+    let fmt = v4l::Format::new(width, height, format.fourcc());
+    dev.set_format(&fmt).context("failed to set capture format")?;
+    let applied = dev
+        .format()
+        .context("failed to read back applied capture format")?;
+    Ok((applied.width, applied.height))
+}
+
+/// Decodes one captured buffer into an `RgbImage`, branching on `format`:
+/// MJPG buffers are JPEG-decoded, YUYV buffers are converted directly with
+/// no intermediate JPEG round-trip.
+pub fn decode_frame(buf: &[u8], format: PixelFormat, width: u32, height: u32) -> Result<RgbImage> {
+    match format {
+        PixelFormat::Mjpg => {
+            Ok(image::load_from_memory_with_format(buf, image::ImageFormat::Jpeg)?.to_rgb8())
+        }
+        PixelFormat::Yuyv => yuyv_to_rgb(buf, width, height),
+    }
+}
+
+/// Converts a raw YUYV422 buffer (`Y0 U0 Y1 V0` per pixel pair, row-major,
+/// no padding) to RGB24 using the standard BT.601 conversion.
+fn yuyv_to_rgb(buf: &[u8], width: u32, height: u32) -> Result<RgbImage> {
+    let row_bytes = width as usize * 2;
+    anyhow::ensure!(
+        buf.len() >= row_bytes * height as usize,
+        "YUYV buffer too small for {}x{}",
+        width,
+        height
+    );
+
+    let mut img = RgbImage::new(width, height);
+    for y in 0..height as usize {
+        let row = &buf[y * row_bytes..(y + 1) * row_bytes];
+        for (pair_idx, pair) in row.chunks_exact(4).enumerate() {
+            let (y0, u, y1, v) = (pair[0], pair[1], pair[2], pair[3]);
+            let x0 = pair_idx * 2;
+            img.put_pixel(x0 as u32, y as u32, yuv_to_rgb_pixel(y0, u, v));
+            img.put_pixel((x0 + 1) as u32, y as u32, yuv_to_rgb_pixel(y1, u, v));
+        }
+    }
+    Ok(img)
+}
+
+fn yuv_to_rgb_pixel(y: u8, u: u8, v: u8) -> image::Rgb<u8> {
+    let c = y as f32 - 16.0;
+    let d = u as f32 - 128.0;
+    let e = v as f32 - 128.0;
+
+    let r = 1.164 * c + 1.596 * e;
+    let g = 1.164 * c - 0.392 * d - 0.813 * e;
+    let b = 1.164 * c + 2.017 * d;
+
+    image::Rgb([
+        r.clamp(0.0, 255.0) as u8,
+        g.clamp(0.0, 255.0) as u8,
+        b.clamp(0.0, 255.0) as u8,
+    ])
+}
@@ -0,0 +1,357 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+use image::RgbImage;
+use serde::Deserialize;
+use url::Url;
+
+use crate::audio::{extract_channel, AudioOptions};
+
+/// Which video codec/pixel format the [`Encoder`] configures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EncodeProfile {
+    /// Software H.264 in YUV420P; there is no VAAPI path (see
+    /// [`select_video_codec`]).
+    /// Lossy and chroma-subsampled, but cheap — the default.
+    #[default]
+    H264,
+    /// FFV1 in YUV444P, intra-only (GOP of 1). Mathematically lossless,
+    /// suitable as an archival master or an intermediate before later
+    /// transcoding. Pair with an `.mkv` [`Output::File`].
+    LosslessFfv1,
+}
+
+impl EncodeProfile {
+    fn pixel_format(self) -> ffmpeg::format::Pixel {
+        match self {
+            EncodeProfile::H264 => ffmpeg::format::Pixel::YUV420P,
+            EncodeProfile::LosslessFfv1 => ffmpeg::format::Pixel::YUV444P,
+        }
+    }
+}
+
+/// Where the encoded stream ends up: a finished file, or a live endpoint
+/// that a media server consumes while the capture loop is still running.
+pub enum Output {
+    /// A regular MP4/MKV file written to disk.
+    File(PathBuf),
+    /// Push the stream to an RTSP server over TCP.
+    Rtsp(Url),
+    /// Emit a segmented HLS playlist at the given directory/`.m3u8` path.
+    Hls(PathBuf),
+}
+
+impl Output {
+    fn open(&self) -> Result<ffmpeg::format::context::Output> {
+        match self {
+            Output::File(path) => ffmpeg::format::output(path)
+                .with_context(|| format!("failed to open {} for writing", path.display())),
+            Output::Rtsp(url) => ffmpeg::format::output_as(url.as_str(), "rtsp")
+                .with_context(|| format!("failed to open RTSP output {}", url)),
+            Output::Hls(path) => ffmpeg::format::output_as(path, "hls")
+                .with_context(|| format!("failed to open HLS output {}", path.display())),
+        }
+    }
+
+    /// Muxer-specific options passed to `write_header_with` (e.g. HLS
+    /// segment duration, RTSP transport).
+    fn container_options(&self) -> ffmpeg::Dictionary {
+        let mut dict = ffmpeg::Dictionary::new();
+        match self {
+            Output::File(_) => {}
+            Output::Rtsp(_) => {
+                dict.set("rtsp_transport", "tcp");
+            }
+            Output::Hls(_) => {
+                dict.set("hls_time", "2");
+                dict.set("hls_flags", "delete_segments");
+            }
+        }
+        dict
+    }
+}
+
+/// Reinterprets an `f32` sample buffer as the raw little-endian bytes
+/// ffmpeg's `Audio` frame data expects.
+fn f32_samples_as_bytes(samples: &[f32]) -> &[u8] {
+    let len = std::mem::size_of_val(samples);
+    unsafe { std::slice::from_raw_parts(samples.as_ptr() as *const u8, len) }
+}
+
+/// AAC audio stream state tracked alongside the video stream.
+struct AudioStream {
+    encoder: ffmpeg::encoder::Audio,
+    stream_index: usize,
+    mono_from_channel: Option<usize>,
+    source_channels: u16,
+    samples_written: i64,
+}
+
+/// Picks the codec for `profile`: `ffv1` for [`EncodeProfile::LosslessFfv1`],
+/// otherwise software `libx264` for [`EncodeProfile::H264`].
+///
+/// There is no `h264_vaapi` path here despite the `vaapi` feature flag
+/// existing: driving VAAPI correctly means creating an `AVHWDeviceContext`
+/// and an `AVHWFramesContext` and attaching the latter to the encoder's
+/// `hw_frames_ctx` before it opens, none of which `ffmpeg-next`'s safe API
+/// exposes — it has to go through raw `ffmpeg-sys-next` calls. An earlier
+/// version of this function "preflighted" `h264_vaapi` by opening it without
+/// a `hw_frames_ctx`, which can't succeed any more than the real encode path
+/// could, so it always silently fell back to `libx264` — worse than no
+/// VAAPI support at all, since it looked like a working feature. Until the
+/// unsafe context plumbing is written, `vaapi` is a reserved feature name
+/// with no effect; every profile encodes in software.
+fn select_video_codec(profile: EncodeProfile) -> Result<ffmpeg::codec::Codec> {
+    if profile == EncodeProfile::LosslessFfv1 {
+        return ffmpeg::encoder::find_by_name("ffv1").context("ffv1 encoder not available");
+    }
+
+    ffmpeg::encoder::find(ffmpeg::codec::Id::H264).context("libx264 encoder not available")
+}
+
+/// In-process video encoder backed by `ffmpeg-next`, configured per
+/// [`EncodeProfile`] (lossy H.264 by default, or lossless FFV1).
+///
+/// Replaces the old pattern of piping `RgbImage::into_raw()` bytes into an
+/// `ffmpeg` child process over stdin: frames are converted RGB24 -> the
+/// profile's pixel format with an in-process scaler and handed straight to
+/// `encoder::Video`, which avoids the per-frame pipe copy and the external
+/// binary dependency.
+pub struct Encoder {
+    octx: ffmpeg::format::context::Output,
+    encoder: ffmpeg::encoder::Video,
+    scaler: ffmpeg::software::scaling::Context,
+    stream_index: usize,
+    frame_in: ffmpeg::util::frame::Video,
+    frame_out: ffmpeg::util::frame::Video,
+    audio: Option<AudioStream>,
+}
+
+impl Encoder {
+    /// Opens `output` for writing and configures a video stream at
+    /// `width`x`height`/`fps` per `profile`, with no audio track.
+    pub fn new(
+        output: Output,
+        width: u32,
+        height: u32,
+        fps: u32,
+        profile: EncodeProfile,
+    ) -> Result<Self> {
+        Self::with_audio(output, width, height, fps, profile, None, None)
+    }
+
+    /// Like [`Encoder::new`], but also opens an AAC audio stream muxed into
+    /// the same container when `audio_source` is given. `audio_source` is
+    /// `(sample_rate, channels)` as reported by the capture device; `options`
+    /// controls channel extraction (e.g. `mono_from_channel`).
+    pub fn with_audio(
+        output: Output,
+        width: u32,
+        height: u32,
+        fps: u32,
+        profile: EncodeProfile,
+        audio_source: Option<(u32, u16)>,
+        options: Option<AudioOptions>,
+    ) -> Result<Self> {
+        ffmpeg::init().context("failed to initialize ffmpeg")?;
+
+        let container_options = output.container_options();
+        let mut octx = output.open()?;
+        let global_header = octx
+            .format()
+            .flags()
+            .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+        let codec = select_video_codec(profile)?;
+        let mut ost = octx.add_stream(codec)?;
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()?;
+        encoder.set_width(width);
+        encoder.set_height(height);
+        encoder.set_format(profile.pixel_format());
+        encoder.set_time_base(ffmpeg::Rational(1, fps as i32));
+        if profile == EncodeProfile::LosslessFfv1 {
+            // Intra-only: every frame is a keyframe, so nothing is lost to
+            // inter-frame prediction either.
+            encoder.set_gop(1);
+        }
+        if global_header {
+            encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+        }
+
+        let opened = if profile == EncodeProfile::LosslessFfv1 {
+            let mut codec_options = ffmpeg::Dictionary::new();
+            codec_options.set("level", "3");
+            encoder
+                .open_as_with(codec, codec_options)
+                .context("failed to open video encoder")?
+        } else {
+            encoder
+                .open_as(codec)
+                .context("failed to open video encoder")?
+        };
+        ost.set_parameters(&opened);
+        let stream_index = ost.index();
+
+        let audio = match audio_source {
+            Some((sample_rate, source_channels)) => {
+                let audio_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
+                    .context("AAC encoder not available")?;
+                let mut aost = octx.add_stream(audio_codec)?;
+
+                let mono_from_channel = options.as_ref().and_then(|o| o.mono_from_channel);
+                let out_channel_layout = if mono_from_channel.is_some() {
+                    ffmpeg::channel_layout::ChannelLayout::MONO
+                } else {
+                    ffmpeg::channel_layout::ChannelLayout::default(source_channels as i32)
+                };
+
+                let mut audio_encoder = ffmpeg::codec::context::Context::new_with_codec(audio_codec)
+                    .encoder()
+                    .audio()?;
+                audio_encoder.set_rate(sample_rate as i32);
+                audio_encoder.set_channel_layout(out_channel_layout);
+                audio_encoder.set_format(ffmpeg::format::Sample::F32(
+                    ffmpeg::format::sample::Type::Packed,
+                ));
+                if global_header {
+                    audio_encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+                }
+
+                let opened_audio = audio_encoder
+                    .open_as(audio_codec)
+                    .context("failed to open audio encoder")?;
+                aost.set_parameters(&opened_audio);
+
+                Some(AudioStream {
+                    encoder: opened_audio,
+                    stream_index: aost.index(),
+                    mono_from_channel,
+                    source_channels,
+                    samples_written: 0,
+                })
+            }
+            None => None,
+        };
+
+        octx.write_header_with(container_options)?;
+
+        let scaler = ffmpeg::software::scaling::Context::get(
+            ffmpeg::format::Pixel::RGB24,
+            width,
+            height,
+            profile.pixel_format(),
+            width,
+            height,
+            ffmpeg::software::scaling::Flags::BILINEAR,
+        )?;
+
+        Ok(Self {
+            octx,
+            encoder: opened,
+            scaler,
+            stream_index,
+            frame_in: ffmpeg::util::frame::Video::new(ffmpeg::format::Pixel::RGB24, width, height),
+            frame_out: ffmpeg::util::frame::Video::new(profile.pixel_format(), width, height),
+            audio,
+        })
+    }
+
+    /// Converts `img` to the encoder's pixel format and hands it off,
+    /// tagged with presentation timestamp `pts` (in units of the encoder's
+    /// `1/fps` time base, i.e. a frame index).
+    pub fn push_frame(&mut self, img: &RgbImage, pts: i64) -> Result<()> {
+        // `frame_in`'s rows are padded to ffmpeg's 32-byte alignment
+        // (`av_frame_get_buffer`), so its stride can exceed `width * 3` —
+        // a flat copy from `img`'s tightly-packed buffer would misalign
+        // every row after the first (and panic outright once the stride
+        // differs enough to overrun `img`'s buffer). Copy row by row instead.
+        let row_bytes = img.width() as usize * 3;
+        let stride = self.frame_in.stride(0);
+        let dst = self.frame_in.data_mut(0);
+        for (row, src_row) in img.as_raw().chunks_exact(row_bytes).enumerate() {
+            dst[row * stride..row * stride + row_bytes].copy_from_slice(src_row);
+        }
+        self.scaler.run(&self.frame_in, &mut self.frame_out)?;
+        self.frame_out.set_pts(Some(pts));
+
+        self.encoder
+            .send_frame(&self.frame_out)
+            .context("failed to send frame to encoder")?;
+        self.drain_encoder()
+    }
+
+    /// Encodes a buffer of interleaved `f32` audio samples (as captured from
+    /// the input device) and muxes the resulting AAC packets. When the
+    /// encoder was configured with `mono_from_channel`, only that channel is
+    /// kept. No-op if the encoder has no audio stream.
+    pub fn push_audio_samples(&mut self, interleaved: &[f32]) -> Result<()> {
+        let Some(audio) = self.audio.as_mut() else {
+            return Ok(());
+        };
+
+        let (samples, out_channels): (Vec<f32>, u16) = match audio.mono_from_channel {
+            Some(channel) => (
+                extract_channel(interleaved, audio.source_channels, channel),
+                1,
+            ),
+            None => (interleaved.to_vec(), audio.source_channels),
+        };
+        // `samples` is still interleaved across `out_channels` channels;
+        // ffmpeg's frame wants the per-channel sample count.
+        let samples_per_channel = samples.len() / out_channels as usize;
+
+        let mut frame = ffmpeg::util::frame::Audio::new(
+            audio.encoder.format(),
+            samples_per_channel,
+            audio.encoder.channel_layout(),
+        );
+        frame.data_mut(0)[..samples.len() * 4]
+            .copy_from_slice(f32_samples_as_bytes(&samples));
+        frame.set_pts(Some(audio.samples_written));
+        audio.samples_written += samples_per_channel as i64;
+
+        audio
+            .encoder
+            .send_frame(&frame)
+            .context("failed to send frame to audio encoder")?;
+        self.drain_audio_encoder()
+    }
+
+    /// Flushes the encoder(s) and writes the trailer, finalizing the file.
+    pub fn finish(mut self) -> Result<()> {
+        self.encoder.send_eof()?;
+        self.drain_encoder()?;
+        if let Some(audio) = self.audio.as_mut() {
+            audio.encoder.send_eof()?;
+            self.drain_audio_encoder()?;
+        }
+        self.octx.write_trailer()?;
+        Ok(())
+    }
+
+    fn drain_encoder(&mut self) -> Result<()> {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(self.stream_index);
+            packet.write_interleaved(&mut self.octx)?;
+        }
+        Ok(())
+    }
+
+    fn drain_audio_encoder(&mut self) -> Result<()> {
+        let Some(audio) = self.audio.as_mut() else {
+            return Ok(());
+        };
+        let mut packet = ffmpeg::Packet::empty();
+        while audio.encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(audio.stream_index);
+            packet.write_interleaved(&mut self.octx)?;
+        }
+        Ok(())
+    }
+}
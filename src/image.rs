@@ -1,7 +1,6 @@
 use std::thread;
 use std::time::Duration;
 use v4l::prelude::*;
-use v4l::FourCC;
 use v4l::video::Capture;
 use v4l::io::traits::CaptureStream;
 use v4l::buffer::Type;
@@ -9,13 +8,18 @@ use image::{Rgb}; // Keep only what you use
 use rusttype::{Font, Scale};
 use anyhow::Result;
 
+use camera_matrixifier::capture;
+
 fn main() -> Result<()> {
     let device_path = "/dev/video0";
     let dev = v4l::Device::with_path(device_path)?;
 
-    // Set the format
-    let format = v4l::Format::new(1280, 720, FourCC::new(b"MJPG"));
-    dev.set_format(&format)?;
+    // Negotiate MJPG if offered at this resolution, otherwise fall back to YUYV
+    let (width, height) = (1280, 720);
+    let pixel_format = capture::negotiate_format(&dev, width, height)?;
+    // The driver may clamp/round the requested resolution; decode against
+    // whatever it actually applied, not what was requested.
+    let (width, height) = capture::apply_format(&dev, pixel_format, width, height)?;
 
     // Memory-mapped capture stream
     let mut stream = MmapStream::new(&dev, Type::VideoCapture)?;
@@ -29,9 +33,8 @@ fn main() -> Result<()> {
         // Capture a frame
         let (buf, _) = stream.next()?;
 
-        // Decode MJPG to RGB
-        let img = image::load_from_memory_with_format(&buf, image::ImageFormat::Jpeg)?
-            .to_rgb8();
+        // Decode the negotiated pixel format to RGB
+        let img = capture::decode_frame(&buf, pixel_format, width, height)?;
 
         let width = img.width();
         let height = img.height();
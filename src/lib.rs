@@ -0,0 +1,5 @@
+pub mod audio;
+pub mod capture;
+pub mod encoder;
+pub mod project;
+pub mod source;
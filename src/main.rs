@@ -1,14 +1,12 @@
-use std::process::{Command, Stdio};
-use std::io::Write;
-use v4l::prelude::*;
-use v4l::FourCC;
-use v4l::video::Capture;
-use v4l::io::traits::CaptureStream;
-use v4l::buffer::Type;
-use image::{Rgb, RgbImage, ImageBuffer}; 
+use std::path::Path;
+
+use image::{Rgb, RgbImage, ImageBuffer};
 use rusttype::{Font, Scale};
 use anyhow::Result;
 
+use camera_matrixifier::encoder::{EncodeProfile, Encoder, Output};
+use camera_matrixifier::source::{self, Source, TestPatternSource, V4lSource};
+
 // ASCII conversion settings
 const RESIZED_WIDTH: u32 = 80; // 160
 const RESIZED_HEIGHT: u32 = 45; // 90
@@ -99,42 +97,28 @@ fn apply_blue_mask(img: &mut RgbImage) {
 }
 
 fn main() -> Result<()> {
-    let device_path = "/dev/video0";
-    let dev = v4l::Device::with_path(device_path)?;
-    
-    // Set the format
-    let format = v4l::Format::new(320, 180, FourCC::new(b"MJPG"));
-    dev.set_format(&format)?;
-    
-    // Memory-mapped capture stream
-    let mut stream = MmapStream::new(&dev, Type::VideoCapture)?;
-    
+    let (capture_width, capture_height) = (320, 180);
+
+    // `--input test` swaps the real camera for a synthetic test pattern so
+    // the rest of the pipeline can be exercised without hardware.
+    let dev_holder;
+    let mut source: Box<dyn Source> = if source::wants_test_pattern() {
+        Box::new(TestPatternSource::new(capture_width, capture_height)?)
+    } else {
+        dev_holder = v4l::Device::with_path("/dev/video0")?;
+        Box::new(V4lSource::new(&dev_holder, capture_width, capture_height)?)
+    };
+
     // Load a font
     let font_data = include_bytes!("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf");
     let font = Font::try_from_bytes(font_data as &[u8]).unwrap();
     let scale = Scale { x: 16.0, y: 16.0 }; // Smaller font for ASCII art
     
-    println!("Starting ffmpeg process...");
-    
-    // Start ffmpeg process with stdin pipe
-    let mut ffmpeg = Command::new("ffmpeg")
-        .arg("-y") // Overwrite output file
-        .arg("-f").arg("rawvideo")  // Input format
-        .arg("-pixel_format").arg("rgb24")  // RGB format
-        .arg("-video_size").arg(format!("{}x{}", OUTPUT_WIDTH, OUTPUT_HEIGHT))
-        .arg("-framerate").arg("10")
-        .arg("-i").arg("pipe:0")  // Read from stdin
-        .arg("-c:v").arg("libx264")
-        .arg("-pix_fmt").arg("yuv420p")
-        .arg("-preset").arg("ultrafast")  // Fast encoding preset
-        .arg("ascii_output.mp4")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()?;
-    
-    let mut stdin = ffmpeg.stdin.take().unwrap();
-    
+    println!("Opening ascii_output.mp4 for encoding...");
+
+    let output = Output::File(Path::new("ascii_output.mp4").to_path_buf());
+    let mut encoder = Encoder::new(output, OUTPUT_WIDTH, OUTPUT_HEIGHT, 10, EncodeProfile::H264)?;
+
     println!("Recording ASCII frames... Press Ctrl+C to stop");
     
     let mut frame_count = 0;
@@ -142,13 +126,9 @@ fn main() -> Result<()> {
     
     // Real-time capture and streaming
     loop {
-        // Capture a frame
-        let (buf, _) = stream.next()?;
-        
-        // Decode MJPG to RGB
-        let mut img = image::load_from_memory_with_format(&buf, image::ImageFormat::Jpeg)?
-            .to_rgb8();
-        
+        // Pull a frame from whichever source was selected
+        let mut img = source.next_frame()?;
+
         // Apply blue masking (similar to Python version)
         apply_blue_mask(&mut img);
         
@@ -169,10 +149,9 @@ fn main() -> Result<()> {
         // Create final image with ASCII text at reduced resolution
         let ascii_image = create_ascii_image(&ascii_art, &font, scale, OUTPUT_WIDTH, OUTPUT_HEIGHT);
         
-        // Convert image to raw RGB bytes and write to ffmpeg stdin
-        let raw_data: Vec<u8> = ascii_image.into_raw();
-        if let Err(e) = stdin.write_all(&raw_data) {
-            eprintln!("Error writing to ffmpeg: {}", e);
+        // Hand the frame to the in-process encoder
+        if let Err(e) = encoder.push_frame(&ascii_image, frame_count as i64) {
+            eprintln!("Error encoding frame: {}", e);
             break;
         }
         
@@ -188,19 +167,9 @@ fn main() -> Result<()> {
         // thread::sleep(Duration::from_millis(33)); // ~30 FPS
     }
     
-    // Close stdin to signal end of input to ffmpeg
-    drop(stdin);
-    
-    println!("Waiting for ffmpeg to finish encoding...");
-    
-    // Wait for ffmpeg to complete
-    let status = ffmpeg.wait()?;
-    
-    if status.success() {
-        println!("Video saved as ascii_output.mp4");
-    } else {
-        println!("Error: ffmpeg process failed with status: {}", status);
-    }
-    
+    println!("Flushing encoder...");
+    encoder.finish()?;
+    println!("Video saved as ascii_output.mp4");
+
     Ok(())
 }
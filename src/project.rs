@@ -0,0 +1,158 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use url::Url;
+
+use crate::encoder::{EncodeProfile, Output};
+
+/// How much a `fast` segment speeds up playback. Mirrors the effect of
+/// ffmpeg's `setpts=PTS/N` on video; audio is not retimed.
+const FAST_FORWARD_FACTOR: f64 = 4.0;
+
+/// A timed text overlay, active while the capture's elapsed time is within
+/// `[start, end)`. Corresponds to one `[[overlay]]` table in the project
+/// file.
+#[derive(Debug, Deserialize)]
+pub struct OverlayConfig {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub position: [f32; 2],
+}
+
+/// Declarative description of a capture job: device, format, output target,
+/// timed overlays, and fast-forward segments. Loaded from a TOML file so
+/// this doesn't need to be recompiled to change (previously this was all
+/// hardcoded per-binary).
+#[derive(Debug, Deserialize)]
+pub struct Project {
+    pub device: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub output: String,
+    /// `"h264"` (default) or `"lossless-ffv1"` — see [`EncodeProfile`].
+    #[serde(default)]
+    pub profile: EncodeProfile,
+    #[serde(default, rename = "overlay")]
+    pub overlays: Vec<OverlayConfig>,
+    /// `[start, end]` time ranges (in seconds, relative to capture start)
+    /// to speed up by [`FAST_FORWARD_FACTOR`]. Expected sorted and
+    /// non-overlapping.
+    ///
+    /// Only remaps the video timeline (see [`Project::effective_time`]) —
+    /// there's no equivalent audio retiming yet, so combining a non-empty
+    /// `fast` with a microphone capture is rejected by the `video` binary
+    /// rather than producing audio that drifts out of sync.
+    #[serde(default)]
+    pub fast: Vec<[f64; 2]>,
+}
+
+impl Project {
+    /// Reads and parses a project file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read project file {}", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("failed to parse project file {}", path.display()))
+    }
+
+    /// The overlay (if any) active at elapsed time `t` (seconds).
+    pub fn overlay_at(&self, t: f64) -> Option<&OverlayConfig> {
+        self.overlays
+            .iter()
+            .find(|overlay| t >= overlay.start && t < overlay.end)
+    }
+
+    /// Maps real elapsed time `t` (seconds) to the output timeline,
+    /// compressing time spent inside `fast` segments by
+    /// [`FAST_FORWARD_FACTOR`]. Use the result, multiplied by `fps`, as the
+    /// frame's presentation timestamp.
+    pub fn effective_time(&self, t: f64) -> f64 {
+        let mut mapped = 0.0;
+        let mut cursor = 0.0;
+        for &[start, end] in &self.fast {
+            if t <= start {
+                break;
+            }
+            mapped += (start - cursor).max(0.0);
+            let segment_end = end.min(t);
+            mapped += (segment_end - start).max(0.0) / FAST_FORWARD_FACTOR;
+            cursor = end;
+            if t <= end {
+                return mapped;
+            }
+        }
+        mapped + (t - cursor).max(0.0)
+    }
+
+    /// Parses `output` into the encoder's `Output` target: an `rtsp://` URL
+    /// streams live, a path ending in `.m3u8` emits an HLS playlist, and
+    /// anything else is treated as an output file.
+    pub fn output_target(&self) -> Result<Output> {
+        if self.output.starts_with("rtsp://") {
+            let url = Url::parse(&self.output)
+                .with_context(|| format!("invalid RTSP output URL: {}", self.output))?;
+            Ok(Output::Rtsp(url))
+        } else if self.output.ends_with(".m3u8") {
+            Ok(Output::Hls(PathBuf::from(&self.output)))
+        } else {
+            Ok(Output::File(PathBuf::from(&self.output)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_with_fast(fast: Vec<[f64; 2]>) -> Project {
+        Project {
+            device: PathBuf::new(),
+            width: 0,
+            height: 0,
+            fps: 0,
+            output: String::new(),
+            profile: EncodeProfile::default(),
+            overlays: Vec::new(),
+            fast,
+        }
+    }
+
+    #[test]
+    fn effective_time_passes_through_with_no_fast_segments() {
+        let project = project_with_fast(vec![]);
+        assert_eq!(project.effective_time(12.5), 12.5);
+    }
+
+    #[test]
+    fn effective_time_unaffected_before_a_fast_segment() {
+        let project = project_with_fast(vec![[10.0, 20.0]]);
+        assert_eq!(project.effective_time(5.0), 5.0);
+    }
+
+    #[test]
+    fn effective_time_compresses_inside_a_fast_segment() {
+        let project = project_with_fast(vec![[10.0, 20.0]]);
+        // 10s real, then 5s into the segment compressed 4x
+        assert_eq!(project.effective_time(15.0), 10.0 + 5.0 / FAST_FORWARD_FACTOR);
+    }
+
+    #[test]
+    fn effective_time_resumes_real_time_after_a_fast_segment() {
+        let project = project_with_fast(vec![[10.0, 20.0]]);
+        // 10s real, the whole 10s segment compressed, then 5s real again
+        assert_eq!(
+            project.effective_time(25.0),
+            10.0 + 10.0 / FAST_FORWARD_FACTOR + 5.0
+        );
+    }
+
+    #[test]
+    fn effective_time_handles_multiple_segments() {
+        let project = project_with_fast(vec![[10.0, 20.0], [30.0, 40.0]]);
+        let expected = 10.0 + 10.0 / FAST_FORWARD_FACTOR + 10.0 + 5.0 / FAST_FORWARD_FACTOR;
+        assert_eq!(project.effective_time(35.0), expected);
+    }
+}
@@ -1,87 +1,96 @@
-use std::process::{Command, Stdio};
-use std::io::Write;
-use std::thread;
-use std::time::Duration;
+use std::path::Path;
 
-use v4l::prelude::*;
-use v4l::FourCC;
-use v4l::video::Capture;
-use v4l::buffer::Type;
-use v4l::io::traits::CaptureStream;
-
-use image::{Rgb, DynamicImage};
-use rusttype::{Font, Scale};
+use rusttype::Font;
 
 use anyhow::Result;
 
+use camera_matrixifier::audio::{AudioCapture, AudioOptions};
+use camera_matrixifier::encoder::Encoder;
+use camera_matrixifier::project::Project;
+use camera_matrixifier::source::{self, draw_text, Source, TestPatternSource, V4lSource};
+
+/// Set to `Some(channel)` to keep only one channel of a stereo capture in
+/// the recorded audio track, e.g. when a lavalier mic is on one channel and
+/// the camera's built-in mic is on the other.
+const MONO_FROM_CHANNEL: Option<usize> = None;
+
 fn main() -> Result<()> {
-    // Open video device
-    let device_path = "/dev/video0";
-    let dev = v4l::Device::with_path(device_path)?;
+    // Load the capture job: device, format, output target, timed overlays
+    // and fast-forward segments. Defaults to `project.toml` in the cwd;
+    // pass a path as the first argument to use a different one.
+    let project_path = std::env::args()
+        .nth(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| "project.toml".to_string());
+    let project = Project::load(Path::new(&project_path))?;
 
-    // Set format
-    let width = 1280;
-    let height = 720;
-    let format = v4l::Format::new(width, height, FourCC::new(b"MJPG"));
-    dev.set_format(&format)?;
+    let width = project.width;
+    let height = project.height;
 
-    // Memory-mapped capture stream
-    let mut stream = v4l::prelude::MmapStream::new(&dev, Type::VideoCapture)?;
+    // `--input test` swaps the real camera for a synthetic test pattern so
+    // the rest of the pipeline can be exercised without hardware.
+    let dev_holder;
+    let mut source: Box<dyn Source> = if source::wants_test_pattern() {
+        Box::new(TestPatternSource::new(width, height)?)
+    } else {
+        dev_holder = v4l::Device::with_path(&project.device)?;
+        Box::new(V4lSource::new(&dev_holder, width, height)?)
+    };
 
-    // Load font
+    // Load font for the timed text overlays
     let font_data = include_bytes!("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf");
     let font = Font::try_from_bytes(font_data as &[u8]).unwrap();
-    let scale = Scale { x: 20.0, y: 20.0 };
 
-    // Spawn ffmpeg subprocess for raw RGB input
-    let mut ffmpeg = Command::new("ffmpeg")
-        .args([
-            "-y",                       // overwrite output
-            "-f", "rawvideo",           // raw video input
-            "-pix_fmt", "rgb24",        // pixel format
-            "-s", &format!("{}x{}", width, height), // resolution
-            "-r", "30",                 // input FPS (match your camera)
-            "-i", "-",                  // input from stdin
-            "-c:v", "libx264",          // encode H.264
-            "-pix_fmt", "yuv420p",      // output pixel format
-            "output.mp4",
-        ])
-        .stdin(Stdio::piped())
-        .spawn()?;
+    // Open the microphone alongside the v4l video stream
+    let audio = AudioCapture::start().ok();
+    let audio_source = audio.as_ref().map(|a| (a.sample_rate, a.channels));
 
-    let ffmpeg_stdin = ffmpeg.stdin.as_mut().unwrap();
+    // `fast` only remaps video pts (see `Project::effective_time`); there's
+    // no equivalent audio retiming, so the combination would silently play
+    // back desynced. Reject it outright rather than producing that.
+    anyhow::ensure!(
+        audio_source.is_none() || project.fast.is_empty(),
+        "project combines a microphone with `fast` segments, which isn't supported yet: \
+         audio has no retiming equivalent to the video pts remap and would drift out of sync"
+    );
+
+    // Open the project's output target for in-process encoding, with audio
+    // muxed in if available
+    let output = project.output_target()?;
+    let mut encoder = Encoder::with_audio(
+        output,
+        width,
+        height,
+        project.fps,
+        project.profile,
+        audio_source,
+        Some(AudioOptions {
+            mono_from_channel: MONO_FROM_CHANNEL,
+        }),
+    )?;
+    let mut frame_count: i64 = 0;
 
     loop {
+        // Drain any audio captured since the last frame and mux it in
+        if let Some(audio) = &audio {
+            while let Ok(samples) = audio.samples.try_recv() {
+                encoder.push_audio_samples(&samples)?;
+            }
+        }
+
         // Capture frame
-        let (buf, _) = stream.next()?;
-        let mut img = image::load_from_memory_with_format(&buf, image::ImageFormat::Jpeg)?
-            .to_rgb8();
+        let mut img = source.next_frame()?;
+        let elapsed = frame_count as f64 / project.fps as f64;
 
-        // Draw text overlay
-        let text = "Hello Rust!";
-        for (i, c) in text.chars().enumerate() {
-            let v_metrics = font.v_metrics(scale);
-            let offset = rusttype::point(10.0 + i as f32 * 15.0, 30.0 + v_metrics.ascent);
-            for glyph in font.layout(&c.to_string(), scale, offset) {
-                if let Some(bb) = glyph.pixel_bounding_box() {
-                    glyph.draw(|x, y, v| {
-                        let px = (bb.min.x + x as i32) as u32;
-                        let py = (bb.min.y + y as i32) as u32;
-                        if px < width && py < height {
-                            let pixel = img.get_pixel_mut(px, py);
-                            *pixel = Rgb([
-                                (v * 255.0) as u8,
-                                pixel[1],
-                                pixel[2],
-                            ]);
-                        }
-                    });
-                }
-            }
+        // Draw whichever overlay is active at the current timestamp
+        if let Some(overlay) = project.overlay_at(elapsed) {
+            draw_text(&mut img, &font, &overlay.text, overlay.position[0], overlay.position[1]);
         }
 
-        // Write raw RGB24 frame to ffmpeg stdin
-        ffmpeg_stdin.write_all(&img.as_raw())?;
+        // Hand the frame to the in-process encoder, remapping its pts
+        // through any `fast` segments so they play back accelerated
+        let pts = (project.effective_time(elapsed) * project.fps as f64) as i64;
+        encoder.push_frame(&img, pts)?;
+        frame_count += 1;
     }
 }
-